@@ -18,6 +18,46 @@ fn is_numric_value(t: &Term) -> bool {
     }
 }
 
+// NB (TAPL 8章) の型。真偽値か自然数のどちらか。
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Ty {
+    Bool,
+    Nat
+}
+
+// TAPL 8章の型付け規則に従って項の型を求める。型が合わない部分項があれば、
+// それを名指ししたエラーを返す。型検査を通れば eval が "stuck" することはない。
+pub fn type_of(t: &Term) -> Result<Ty, String> {
+    use self::Term::*;
+    match *t {
+        True | False => Ok(Ty::Bool),
+        If(ref t1, ref t2, ref t3) => {
+            if type_of(t1)? != Ty::Bool {
+                return Err(format!("guard of conditional is not a boolean: {}", t1.show()));
+            }
+            let ty2 = type_of(t2)?;
+            let ty3 = type_of(t3)?;
+            if ty2 != ty3 {
+                return Err(format!("arms of conditional have different types: {} and {}", t2.show(), t3.show()));
+            }
+            Ok(ty2)
+        }
+        Zero => Ok(Ty::Nat),
+        Succ(ref t) | Pred(ref t) => {
+            if type_of(t)? != Ty::Nat {
+                return Err(format!("argument of succ/pred is not a number: {}", t.show()));
+            }
+            Ok(Ty::Nat)
+        }
+        IsZero(ref t) => {
+            if type_of(t)? != Ty::Nat {
+                return Err(format!("argument of iszero is not a number: {}", t.show()));
+            }
+            Ok(Ty::Bool)
+        }
+    }
+}
+
 fn eval_1step(t: Term) -> Result<Term, &'static str> {
     use self::Term::*;
     match t {
@@ -54,6 +94,165 @@ pub fn eval(t: Term) -> Term {
     }
 }
 
+// eval_1step を繰り返し適用して得られる簡約列を列挙するイテレータ。
+// 最初の要素は t 自身で、どの規則も適用できなくなったところで終わる。
+pub fn steps(t: Term) -> impl Iterator<Item = Term> {
+    ::std::iter::successors(Some(t), |t| eval_1step(t.clone()).ok())
+}
+
+pub fn trace(t: Term) -> String {
+    steps(t).map(|t| t.show()).collect::<Vec<_>>().join(" --> ")
+}
+
+impl Term {
+    // 値になっている数値の項は数字として、それ以外は keyword 形式で表示する。
+    pub fn show(&self) -> String {
+        use self::Term::*;
+        fn numeral_value(t: &Term) -> Option<u32> {
+            match *t {
+                Zero => Some(0),
+                Succ(ref t) => numeral_value(t).map(|n| n + 1),
+                _ => None
+            }
+        }
+        if let Some(n) = numeral_value(self) {
+            return n.to_string();
+        }
+        match *self {
+            True => "true".to_string(),
+            False => "false".to_string(),
+            If(ref t1, ref t2, ref t3) => format!("if {} then {} else {}", t1.show(), t2.show(), t3.show()),
+            Zero => "0".to_string(),
+            Succ(ref t) => format!("succ {}", t.show()),
+            Pred(ref t) => format!("pred {}", t.show()),
+            IsZero(ref t) => format!("iszero {}", t.show())
+        }
+    }
+}
+
+// 具象構文を Term に読み込む再帰下降パーザ。
+// term    ::= "if" term "then" term "else" term
+//           | "succ" aTerm | "pred" aTerm | "iszero" aTerm
+//           | aTerm
+// aTerm   ::= "(" term ")" | "true" | "false" | NUMBER
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Word(String)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' | '\n' | '\r' => { chars.next(); }
+            '(' => { chars.next(); tokens.push(Token::LParen); }
+            ')' => { chars.next(); tokens.push(Token::RParen); }
+            c if c.is_alphanumeric() => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() {
+                        word.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+            _ => return Err(format!("unexpected character: {}", c))
+        }
+    }
+    Ok(tokens)
+}
+
+fn numeral(n: u32) -> Term {
+    let mut t = Term::Zero;
+    for _ in 0..n {
+        t = Term::Succ(box t);
+    }
+    t
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn advance(&mut self) -> Option<&Token> {
+        let t = self.tokens.get(self.pos);
+        self.pos += 1;
+        t
+    }
+    fn expect(&mut self, tok: &Token) -> Result<(), String> {
+        match self.advance() {
+            Some(t) if t == tok => Ok(()),
+            other => Err(format!("expected {:?} but got {:?}", tok, other))
+        }
+    }
+    fn is_word(&self, word: &str) -> bool {
+        match self.peek() {
+            Some(&Token::Word(ref w)) => w == word,
+            _ => false
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<Term, String> {
+        if self.is_word("if") {
+            self.advance();
+            let t1 = self.parse_term()?;
+            self.expect(&Token::Word("then".to_string()))?;
+            let t2 = self.parse_term()?;
+            self.expect(&Token::Word("else".to_string()))?;
+            let t3 = self.parse_term()?;
+            Ok(Term::If(box t1, box t2, box t3))
+        } else if self.is_word("succ") {
+            self.advance();
+            Ok(Term::Succ(box self.parse_atom_term()?))
+        } else if self.is_word("pred") {
+            self.advance();
+            Ok(Term::Pred(box self.parse_atom_term()?))
+        } else if self.is_word("iszero") {
+            self.advance();
+            Ok(Term::IsZero(box self.parse_atom_term()?))
+        } else {
+            self.parse_atom_term()
+        }
+    }
+
+    fn parse_atom_term(&mut self) -> Result<Term, String> {
+        match self.advance() {
+            Some(&Token::LParen) => {
+                let t = self.parse_term()?;
+                self.expect(&Token::RParen)?;
+                Ok(t)
+            }
+            Some(&Token::Word(ref w)) if w == "true" => Ok(Term::True),
+            Some(&Token::Word(ref w)) if w == "false" => Ok(Term::False),
+            Some(&Token::Word(ref w)) if w.chars().all(|c| c.is_digit(10)) => {
+                w.parse().map(numeral).map_err(|_| format!("invalid numeral: {}", w))
+            }
+            other => Err(format!("unexpected token: {:?}", other))
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Term, String> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0 };
+    let t = parser.parse_term()?;
+    if parser.pos != tokens.len() {
+        return Err(format!("unexpected trailing tokens at position {}", parser.pos));
+    }
+    Ok(t)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,4 +289,53 @@ mod tests {
         }
         assert_eq!(eval(Succ(box Zero)), Succ(box Zero));
     }
+
+    #[test]
+    fn test_type_of() {
+        assert_eq!(type_of(&True).unwrap(), Ty::Bool);
+        assert_eq!(type_of(&Succ(box Zero)).unwrap(), Ty::Nat);
+        assert_eq! {
+            type_of(&If(box IsZero(box Zero), box Succ(box Zero), box Zero)).unwrap(),
+            Ty::Nat
+        }
+
+        assert!(type_of(&If(box Zero, box True, box False)).is_err());
+        assert!(type_of(&Succ(box True)).is_err());
+        assert!(type_of(&IsZero(box True)).is_err());
+        assert!(type_of(&If(box True, box Zero, box False)).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("true").unwrap(), True);
+        assert_eq!(parse("0").unwrap(), Zero);
+        assert_eq!(parse("3").unwrap(), Succ(box Succ(box Succ(box Zero))));
+        assert_eq! {
+            parse("if iszero (pred (succ 0)) then true else false").unwrap(),
+            If(box IsZero(box Pred(box Succ(box Zero))), box True, box False)
+        }
+        assert!(parse("if true then true").is_err());
+        assert!(parse("true false").is_err());
+    }
+
+    #[test]
+    fn test_show() {
+        assert_eq!(True.show(), "true");
+        assert_eq!(Succ(box Succ(box Zero)).show(), "2");
+        assert_eq!(If(box True, box Zero, box False).show(), "if true then 0 else false");
+        assert_eq!(Pred(box True).show(), "pred true");
+    }
+
+    #[test]
+    fn test_steps_and_trace() {
+        let t = If(box IsZero(box Pred(box Pred(box Succ(box Zero)))),
+            box If(box True, box Succ(box Zero), box False),
+            box False
+        );
+        let collected: Vec<Term> = steps(t.clone()).collect();
+        assert_eq!(*collected.last().unwrap(), eval(t.clone()));
+        assert!(collected.len() > 1);
+        let expected_trace = collected.iter().map(|t| t.show()).collect::<Vec<_>>().join(" --> ");
+        assert_eq!(trace(t), expected_trace);
+    }
 }
@@ -0,0 +1,277 @@
+// untyped のラムダ項と arith の真偽値/数値を一つの言語にまとめたもの
+// (TAPL の fulluntyped)。`(\x. succ x) 0` や、関数適用を条件式の分岐に
+// 使うような項を、ひとつの Term / eval で扱えるようにする。
+
+use std::rc::Rc;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Term {
+    Var(usize, usize),
+    Abs(String, Rc<Term>),
+    App(Rc<Term>, Rc<Term>),
+    True,
+    False,
+    If(Rc<Term>, Rc<Term>, Rc<Term>),
+    Zero,
+    Succ(Rc<Term>),
+    Pred(Rc<Term>),
+    IsZero(Rc<Term>)
+}
+
+#[derive(Debug, Clone)]
+struct NameBind;
+
+type Binding = NameBind; // naming context (名前付け文脈) p58 6.1
+
+#[derive(Debug, Clone)]
+pub struct Context(Vec<(String, Binding)>);
+
+impl Context {
+    pub fn new<T: AsRef<str>>(names: &[T]) -> Context {
+        Context(names.iter().map(|name| (name.as_ref().to_string(), NameBind)).collect())
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    fn index_to_name(&self, n: usize) -> &String {
+        &self.0[self.len() - 1 - n].0
+    }
+    fn pick_fresh_name(&self, name: &str) -> (Context, String) {
+        let has = self.0.iter().any(|x| name == x.0);
+        if has {
+            self.pick_fresh_name(&format!("{}'", name))
+        } else {
+            let name = name.to_string();
+            let mut newone = self.clone();
+            newone.0.push((name.clone(), NameBind));
+            (newone, name)
+        }
+    }
+}
+
+fn is_numeric_value(t: &Term) -> bool {
+    match *t {
+        Term::Zero => true,
+        Term::Succ(ref t) => is_numeric_value(t),
+        _ => false
+    }
+}
+
+impl Term {
+    pub fn show(&self, ctx: &Context) -> String {
+        use self::Term::*;
+        fn numeral_value(t: &Term) -> Option<u32> {
+            match *t {
+                Zero => Some(0),
+                Succ(ref t) => numeral_value(t).map(|n| n + 1),
+                _ => None
+            }
+        }
+        if let Some(n) = numeral_value(self) {
+            return n.to_string();
+        }
+        match *self {
+            Var(x, n) => {
+                if ctx.len() == n {
+                    ctx.index_to_name(x).clone()
+                } else {
+                    panic!("bad index. Context len is {} but var has {}.", ctx.len(), n)
+                }
+            }
+            Abs(ref name, ref t) => {
+                let (ctx, name) = ctx.pick_fresh_name(name);
+                format!("(λ{}. {})", name, t.show(&ctx))
+            }
+            App(ref t1, ref t2) => format!("({} {})", t1.show(ctx), t2.show(ctx)),
+            True => "true".to_string(),
+            False => "false".to_string(),
+            If(ref t1, ref t2, ref t3) => format!("if {} then {} else {}", t1.show(ctx), t2.show(ctx), t3.show(ctx)),
+            Zero => "0".to_string(),
+            Succ(ref t) => format!("succ {}", t.show(ctx)),
+            Pred(ref t) => format!("pred {}", t.show(ctx)),
+            IsZero(ref t) => format!("iszero {}", t.show(ctx))
+        }
+    }
+
+    fn shift(&self, d: isize) -> Term {
+        fn walk(t: &Term, d: isize, c: usize) -> Term {
+            use self::Term::*;
+            match *t {
+                Var(x, n) => Var(if x >= c {(x as isize + d) as usize} else {x}, (n as isize + d) as usize),
+                Abs(ref name, ref t) => Abs(name.clone(), Rc::new(walk(t, d, c+1))),
+                App(ref t1, ref t2) => App(Rc::new(walk(t1, d, c)), Rc::new(walk(t2, d, c))),
+                True => True,
+                False => False,
+                If(ref t1, ref t2, ref t3) => If(Rc::new(walk(t1, d, c)), Rc::new(walk(t2, d, c)), Rc::new(walk(t3, d, c))),
+                Zero => Zero,
+                Succ(ref t) => Succ(Rc::new(walk(t, d, c))),
+                Pred(ref t) => Pred(Rc::new(walk(t, d, c))),
+                IsZero(ref t) => IsZero(Rc::new(walk(t, d, c)))
+            }
+        }
+        walk(self, d, 0)
+    }
+
+    fn subst(&self, j: usize, ts: &Term) -> Term {
+        fn walk(t: &Term, j: usize, c: usize, ts: &Term) -> Term {
+            use self::Term::*;
+            match *t {
+                Var(x, _) => if x == j+c {ts.shift(c as isize)} else {t.clone()},
+                Abs(ref name, ref t) => Abs(name.clone(), Rc::new(walk(t, j, c+1, ts))),
+                App(ref t1, ref t2) => App(Rc::new(walk(t1, j, c, ts)), Rc::new(walk(t2, j, c, ts))),
+                True => True,
+                False => False,
+                If(ref t1, ref t2, ref t3) => If(Rc::new(walk(t1, j, c, ts)), Rc::new(walk(t2, j, c, ts)), Rc::new(walk(t3, j, c, ts))),
+                Zero => Zero,
+                Succ(ref t) => Succ(Rc::new(walk(t, j, c, ts))),
+                Pred(ref t) => Pred(Rc::new(walk(t, j, c, ts))),
+                IsZero(ref t) => IsZero(Rc::new(walk(t, j, c, ts)))
+            }
+        }
+        walk(self, j, 0, ts)
+    }
+
+    fn subst_top(&self, s: &Term) -> Term {
+        self.subst(0, &s.shift(1)).shift(-1)
+    }
+
+    // 値 (value) は、関数 (abs) と数値 (0 または succ の連なり) と真偽値。
+    fn is_val(&self) -> bool {
+        match *self {
+            Term::Abs(_, _) => true,
+            Term::True | Term::False => true,
+            ref t => is_numeric_value(t)
+        }
+    }
+
+    fn eval1(&self) -> Result<Term, String> {
+        use self::Term::*;
+        match *self {
+            App(ref t1, ref t2) => match t1.as_ref() {
+                &Abs(_, ref t12) if t2.is_val() => Ok(t12.subst_top(t2)),
+                v if v.is_val() => {
+                    let t2 = t2.eval1()?;
+                    Ok(App(Rc::new(v.clone()), Rc::new(t2)))
+                }
+                _ => {
+                    let t1 = t1.eval1()?;
+                    Ok(App(Rc::new(t1), t2.clone()))
+                }
+            },
+            If(ref t1, ref t2, ref t3) => match t1.as_ref() {
+                &True => Ok((**t2).clone()),
+                &False => Ok((**t3).clone()),
+                _ => {
+                    let t1 = t1.eval1()?;
+                    Ok(If(Rc::new(t1), t2.clone(), t3.clone()))
+                }
+            },
+            Succ(ref t) => {
+                let t = t.eval1()?;
+                Ok(Succ(Rc::new(t)))
+            }
+            Pred(ref t) => match t.as_ref() {
+                &Zero => Ok(Zero),
+                &Succ(ref nv) if is_numeric_value(nv) => Ok((**nv).clone()),
+                _ => {
+                    let t = t.eval1()?;
+                    Ok(Pred(Rc::new(t)))
+                }
+            },
+            IsZero(ref t) => match t.as_ref() {
+                &Zero => Ok(True),
+                &Succ(ref nv) if is_numeric_value(nv) => Ok(False),
+                _ => {
+                    let t = t.eval1()?;
+                    Ok(IsZero(Rc::new(t)))
+                }
+            },
+            _ => Err(format!("No rule applies: {:?}", self))
+        }
+    }
+
+    pub fn eval(&self) -> Term {
+        let mut t = self.clone();
+        loop {
+            match t.eval1() {
+                Ok(next) => t = next,
+                Err(_) => return t
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::Term::*;
+
+    fn abs(name: &str, t: Term) -> Term {
+        Abs(name.to_string(), Rc::new(t))
+    }
+
+    fn app(t1: Term, t2: Term) -> Term {
+        App(Rc::new(t1), Rc::new(t2))
+    }
+
+    fn numeral(n: u32) -> Term {
+        let mut t = Zero;
+        for _ in 0..n {
+            t = Succ(Rc::new(t));
+        }
+        t
+    }
+
+    #[test]
+    fn term_is_val() {
+        assert!(True.is_val());
+        assert!(numeral(3).is_val());
+        assert!(abs("x", Var(0, 1)).is_val());
+        assert!(!app(abs("x", Var(0, 1)), True).is_val());
+    }
+
+    #[test]
+    fn term_eval_lambda_and_numbers() {
+        // (λx. succ x) 0 --> succ 0
+        let t = app(abs("x", Succ(Rc::new(Var(0, 1)))), Zero);
+        assert_eq!(t.eval(), numeral(1));
+    }
+
+    #[test]
+    fn term_eval_if_over_application() {
+        // if (λx. x) true then 1 else 2 --> 1
+        let t = If(
+            Rc::new(app(abs("x", Var(0, 1)), True)),
+            Rc::new(numeral(1)),
+            Rc::new(numeral(2))
+        );
+        assert_eq!(t.eval(), numeral(1));
+    }
+
+    #[test]
+    fn term_eval_pred_succ_iszero() {
+        assert_eq!(Pred(Rc::new(numeral(3))).eval(), numeral(2));
+        assert_eq!(IsZero(Rc::new(numeral(0))).eval(), True);
+        assert_eq!(IsZero(Rc::new(numeral(1))).eval(), False);
+    }
+
+    #[test]
+    fn term_shift_and_subst() {
+        // λ.(0 1) に shift 1 すると λ.(0 2)
+        let t = abs("x", app(Var(0, 2), Var(1, 2)));
+        assert_eq!(t.shift(1), abs("x", app(Var(0, 3), Var(2, 3))));
+        // (λx. x) の本体 x に 5 を代入
+        assert_eq!(Var(0, 1).subst_top(&numeral(5)), numeral(5));
+    }
+
+    #[test]
+    fn term_show() {
+        let empty = Context::new::<&str>(&[]);
+        assert_eq!(numeral(2).show(&empty), "2");
+        assert_eq!(True.show(&empty), "true");
+        let t = If(Rc::new(True), Rc::new(numeral(0)), Rc::new(False));
+        assert_eq!(t.show(&empty), "if true then 0 else false");
+        assert_eq!(abs("x", Var(0, 1)).show(&empty), "(λx. x)");
+    }
+}
@@ -0,0 +1,355 @@
+// untyped::Term をインタラクションネットへコンパイルし、相互作用combinator
+// (CON / DUP / ERA) による書き換えで簡約する。β 簡約は CON-CON の annihilation
+// として、共有されたサブタームの複製は DUP agent の commutation として自然に
+// 表現されるため、同じ簡約基が何度も再計算されることがない (Lévy 最適簡約)。
+//
+// 表現:
+//   - 各 agent は principal port (slot 0) と 0〜2 個の auxiliary port を持つ。
+//   - CON agent は λ抽象と適用の両方に使う。Abs として使うときは principal が
+//     値そのもの (外から関数として使われるときに繋がる)、aux1 が束縛変数の
+//     供給口、aux2 が本体。App として使うときは principal が関数側、aux1 が
+//     引数、aux2 が結果 (呼び出し元から見える値)。
+//   - DUP agent はラベル付きで、束縛変数が複数回使われる箇所のファンアウトを
+//     表す。
+//   - ERA agent は使われない束縛変数を消費する。
+
+use std::rc::Rc;
+use super::untyped::Term;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Port {
+    cell: usize,
+    slot: usize
+}
+
+fn principal(cell: usize) -> Port { Port { cell: cell, slot: 0 } }
+fn aux1(cell: usize) -> Port { Port { cell: cell, slot: 1 } }
+fn aux2(cell: usize) -> Port { Port { cell: cell, slot: 2 } }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Con,
+    Dup(usize),
+    Era,
+    // 項全体の「外側」を表す番人 agent。principal port がひとつあるだけで、
+    // 他の agent と反応することはなく、簡約が終わったあとにどこから
+    // read_back を始めればよいかを追いかけるための目印として使う。
+    Root
+}
+
+fn arity(kind: Kind) -> usize {
+    match kind {
+        Kind::Con | Kind::Dup(_) => 3,
+        Kind::Era | Kind::Root => 1
+    }
+}
+
+struct Net {
+    kinds: Vec<Kind>,
+    names: Vec<Option<String>>,
+    links: Vec<Vec<Option<Port>>>,
+    alive: Vec<bool>
+}
+
+impl Net {
+    fn new() -> Net {
+        Net { kinds: Vec::new(), names: Vec::new(), links: Vec::new(), alive: Vec::new() }
+    }
+
+    fn alloc(&mut self, kind: Kind) -> usize {
+        self.alloc_named(kind, None)
+    }
+
+    fn alloc_named(&mut self, kind: Kind, name: Option<String>) -> usize {
+        let id = self.kinds.len();
+        self.kinds.push(kind);
+        self.names.push(name);
+        self.links.push(vec![None; arity(kind)]);
+        self.alive.push(true);
+        id
+    }
+
+    fn connect(&mut self, a: Port, b: Port) {
+        self.links[a.cell][a.slot] = Some(b);
+        self.links[b.cell][b.slot] = Some(a);
+    }
+
+    fn peer(&self, p: Port) -> Option<Port> {
+        self.links[p.cell][p.slot]
+    }
+}
+
+// 束縛変数の出現は、最終的な接続先がまだ決まっていないので、1 port だけの
+// 仮の ERA agent を置いておき、あとで finish_binder が本当の接続先へ
+// redirect する。
+fn compile(t: &Term, net: &mut Net, env: &mut Vec<Vec<usize>>, next_label: &mut usize) -> Port {
+    match *t {
+        Term::Var(x, _) => {
+            let placeholder = net.alloc(Kind::Era);
+            let depth = env.len();
+            env[depth - 1 - x].push(placeholder);
+            principal(placeholder)
+        }
+        Term::Abs(ref name, ref body) => {
+            let a = net.alloc_named(Kind::Con, Some(name.clone()));
+            env.push(Vec::new());
+            let body_port = compile(body, net, env, next_label);
+            net.connect(aux2(a), body_port);
+            let uses = env.pop().unwrap();
+            finish_binder(net, next_label, aux1(a), uses);
+            principal(a)
+        }
+        Term::App(ref t1, ref t2) => {
+            let a = net.alloc(Kind::Con);
+            let f_port = compile(t1, net, env, next_label);
+            net.connect(principal(a), f_port);
+            let arg_port = compile(t2, net, env, next_label);
+            net.connect(aux1(a), arg_port);
+            aux2(a)
+        }
+    }
+}
+
+fn finish_binder(net: &mut Net, next_label: &mut usize, binder_port: Port, uses: Vec<usize>) {
+    match uses.len() {
+        0 => {
+            let e = net.alloc(Kind::Era);
+            net.connect(binder_port, principal(e));
+        }
+        1 => {
+            let target = net.peer(principal(uses[0])).expect("unconnected variable occurrence");
+            net.connect(target, binder_port);
+            // placeholder はもう使われないので、後の active pair 検出が
+            // そこに残った古い (片側だけの) リンクを拾わないよう殺しておく。
+            net.alive[uses[0]] = false;
+        }
+        _ => {
+            let label = *next_label;
+            *next_label += 1;
+            let targets: Vec<Port> = uses.iter()
+                .map(|&p| net.peer(principal(p)).expect("unconnected variable occurrence"))
+                .collect();
+            let top = build_fanout(net, label, &targets);
+            net.connect(binder_port, top);
+            for &p in &uses {
+                net.alive[p] = false;
+            }
+        }
+    }
+}
+
+fn build_fanout(net: &mut Net, label: usize, targets: &[Port]) -> Port {
+    if targets.len() == 1 {
+        targets[0]
+    } else {
+        let mid = targets.len() / 2;
+        let d = net.alloc(Kind::Dup(label));
+        let left = build_fanout(net, label, &targets[..mid]);
+        net.connect(aux1(d), left);
+        let right = build_fanout(net, label, &targets[mid..]);
+        net.connect(aux2(d), right);
+        principal(d)
+    }
+}
+
+fn is_same_family(k1: Kind, k2: Kind) -> bool {
+    match (k1, k2) {
+        (Kind::Con, Kind::Con) => true,
+        (Kind::Dup(l1), Kind::Dup(l2)) => l1 == l2,
+        (Kind::Era, Kind::Era) => true,
+        _ => false
+    }
+}
+
+// principal port 同士が同じ agent family につながっている (annihilation が
+// 可能な) active pair を書き換える。新しい agent は作らず、つなぎ直した
+// 先の agent の id を返す。
+//
+// c1 や c2 の aux port が自分自身 (あるいは相手) の aux port に直接つながって
+// いる退化したケース (例えば λx.x の aux1<->aux2 自己ループ) がある。このとき
+// x1/x2/y1/y2 をそのまま繋ぐと、消去される c1/c2 の port を指したままの
+// dangling な辺が残ってしまう。そこで繋ぎ先が c1/c2 自身の aux port だった
+// 場合は、対応するもう一方の値に置き換えてから接続する。
+fn annihilate(net: &mut Net, c1: usize, c2: usize) -> Vec<usize> {
+    if net.kinds[c1] == Kind::Era {
+        return Vec::new();
+    }
+    let x1 = net.peer(aux1(c1)).expect("annihilate: disconnected aux1");
+    let x2 = net.peer(aux2(c1)).expect("annihilate: disconnected aux2");
+    let y1 = net.peer(aux1(c2)).expect("annihilate: disconnected aux1");
+    let y2 = net.peer(aux2(c2)).expect("annihilate: disconnected aux2");
+
+    let resolve = |p: Port| -> Port {
+        if p == aux1(c1) { y1 }
+        else if p == aux2(c1) { y2 }
+        else if p == aux1(c2) { x1 }
+        else if p == aux2(c2) { x2 }
+        else { p }
+    };
+    let x1 = resolve(x1);
+    let x2 = resolve(x2);
+    let y1 = resolve(y1);
+    let y2 = resolve(y2);
+
+    net.connect(x1, y1);
+    net.connect(x2, y2);
+    vec![x1.cell, x2.cell, y1.cell, y2.cell]
+}
+
+// 異なる family の agent 同士 (CON/DUP、あるいはラベルの異なる DUP 同士) が
+// 出会ったときの commutation。互いを複製しあう。
+//
+// annihilate と同様、c1 や c2 の aux port が自己ループしている退化したケース
+// では、繋ぎ先を新しく複製した agent の principal port に置き換える。
+fn commute(net: &mut Net, c1: usize, c2: usize) -> Vec<usize> {
+    let k1 = net.kinds[c1];
+    let k2 = net.kinds[c2];
+    let name1 = net.names[c1].clone();
+    let name2 = net.names[c2].clone();
+    let c1_aux1 = net.peer(aux1(c1)).expect("commute: disconnected aux1");
+    let c1_aux2 = net.peer(aux2(c1)).expect("commute: disconnected aux2");
+    let c2_aux1 = net.peer(aux1(c2)).expect("commute: disconnected aux1");
+    let c2_aux2 = net.peer(aux2(c2)).expect("commute: disconnected aux2");
+
+    let a1 = net.alloc_named(k1, name1.clone());
+    let a2 = net.alloc_named(k1, name1);
+    let b1 = net.alloc_named(k2, name2.clone());
+    let b2 = net.alloc_named(k2, name2);
+
+    let resolve = |p: Port| -> Port {
+        if p == aux1(c1) { principal(b1) }
+        else if p == aux2(c1) { principal(b2) }
+        else if p == aux1(c2) { principal(a1) }
+        else if p == aux2(c2) { principal(a2) }
+        else { p }
+    };
+
+    net.connect(resolve(c1_aux1), principal(b1));
+    net.connect(resolve(c1_aux2), principal(b2));
+    net.connect(resolve(c2_aux1), principal(a1));
+    net.connect(resolve(c2_aux2), principal(a2));
+
+    net.connect(aux1(a1), aux1(b1));
+    net.connect(aux2(a1), aux1(b2));
+    net.connect(aux1(a2), aux2(b1));
+    net.connect(aux2(a2), aux2(b2));
+
+    vec![a1, a2, b1, b2]
+}
+
+// ERA が他の agent と出会ったときは、その agent の auxiliary port すべてに
+// 新しい ERA を繋いで消去を伝播する。other が自己ループしている場合は、生成
+// した ERA 同士を直接繋ぐ (resolve で置き換える) ことで dangling な辺を避ける。
+fn erase(net: &mut Net, other: usize) -> Vec<usize> {
+    let o_aux1 = net.peer(aux1(other)).expect("erase: disconnected aux1");
+    let o_aux2 = net.peer(aux2(other)).expect("erase: disconnected aux2");
+    let e1 = net.alloc(Kind::Era);
+    let e2 = net.alloc(Kind::Era);
+
+    let resolve = |p: Port| -> Port {
+        if p == aux1(other) { principal(e1) }
+        else if p == aux2(other) { principal(e2) }
+        else { p }
+    };
+
+    let t1 = resolve(o_aux1);
+    let t2 = resolve(o_aux2);
+    net.connect(t1, principal(e1));
+    net.connect(t2, principal(e2));
+    vec![e1, e2, t1.cell, t2.cell]
+}
+
+fn apply_rule(net: &mut Net, c1: usize, c2: usize) -> Vec<usize> {
+    let k1 = net.kinds[c1];
+    let k2 = net.kinds[c2];
+    match (k1, k2) {
+        (Kind::Era, Kind::Era) => Vec::new(),
+        (Kind::Era, _) => erase(net, c2),
+        (_, Kind::Era) => erase(net, c1),
+        _ if is_same_family(k1, k2) => annihilate(net, c1, c2),
+        _ => commute(net, c1, c2)
+    }
+}
+
+fn reduce_to_normal_form(net: &mut Net) {
+    let mut worklist: Vec<usize> = (0..net.kinds.len()).collect();
+    while let Some(c1) = worklist.pop() {
+        if !net.alive[c1] || net.kinds[c1] == Kind::Root {
+            continue;
+        }
+        if let Some(peer) = net.peer(principal(c1)) {
+            if peer.slot == 0 && peer.cell != c1 && net.alive[peer.cell] && net.kinds[peer.cell] != Kind::Root {
+                let c2 = peer.cell;
+                net.alive[c1] = false;
+                net.alive[c2] = false;
+                let touched = apply_rule(net, c1, c2);
+                worklist.extend(touched);
+            }
+        }
+    }
+}
+
+// port で読める値を Term へ読み戻す。abs_stack は、今読んでいる位置を囲む
+// Abs agent の id を外側から順に積んだもの (末尾が最も内側。de Bruijn
+// index の計算に使う)。
+fn read_back(net: &Net, port: Port, abs_stack: &mut Vec<usize>) -> Term {
+    match (net.kinds[port.cell], port.slot) {
+        (Kind::Con, 0) => {
+            abs_stack.push(port.cell);
+            let body_port = net.peer(aux2(port.cell)).expect("read_back: abs body not connected");
+            let body = read_back(net, body_port, abs_stack);
+            abs_stack.pop();
+            let name = net.names[port.cell].clone().unwrap_or_else(|| "x".to_string());
+            Term::Abs(name, Rc::new(body))
+        }
+        (Kind::Con, 2) => {
+            let f_port = net.peer(principal(port.cell)).expect("read_back: app function not connected");
+            let arg_port = net.peer(aux1(port.cell)).expect("read_back: app argument not connected");
+            let f = read_back(net, f_port, abs_stack);
+            let a = read_back(net, arg_port, abs_stack);
+            Term::App(Rc::new(f), Rc::new(a))
+        }
+        (Kind::Con, 1) => {
+            let i = abs_stack.iter().rev().position(|&c| c == port.cell)
+                .expect("read_back: variable occurrence escaped its binder");
+            Term::Var(i, abs_stack.len())
+        }
+        (Kind::Dup(_), 1) | (Kind::Dup(_), 2) => {
+            // 共有されたままの値は、DUP を素通りして本体を読めばよい。
+            let through = net.peer(principal(port.cell)).expect("read_back: dup principal not connected");
+            read_back(net, through, abs_stack)
+        }
+        (kind, slot) => panic!("read_back: unexpected port {:?} slot {}", kind, slot)
+    }
+}
+
+fn is_closed_at(t: &Term, depth: usize) -> bool {
+    match *t {
+        Term::Var(x, _) => x < depth,
+        Term::Abs(_, ref body) => is_closed_at(body, depth + 1),
+        Term::App(ref t1, ref t2) => is_closed_at(t1, depth) && is_closed_at(t2, depth)
+    }
+}
+
+pub fn is_closed(t: &Term) -> bool {
+    is_closed_at(t, 0)
+}
+
+// 閉じた項をインタラクションネットにコンパイルし、正規形まで簡約してから
+// 項に読み戻す。呼び出し側 (Term::eval_optimal) で閉じていることを保証する。
+pub fn eval_optimal(t: &Term) -> Term {
+    let mut net = Net::new();
+    let mut env = Vec::new();
+    let mut next_label = 0;
+    let root = compile(t, &mut net, &mut env, &mut next_label);
+    // root port をそのまま晒しておくと、それが書き換えに巻き込まれたときに
+    // 繋ぎ先を見失うので、番人 agent を挟んで追跡できるようにしておく。
+    let root_marker = net.alloc(Kind::Root);
+    net.connect(root, principal(root_marker));
+
+    reduce_to_normal_form(&mut net);
+
+    let final_root = net.peer(principal(root_marker)).expect("eval_optimal: root lost its connection");
+    let mut abs_stack = Vec::new();
+    read_back(&net, final_root, &mut abs_stack)
+}
@@ -8,6 +8,14 @@ pub enum Term {
     App(Rc<Term>, Rc<Term>)
 }
 
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Strategy {
+    CallByValue,
+    CallByName,
+    // リーフを含めた最左最外の簡約基を簡約する。β 正規形まで簡約が進む。
+    NormalOrder
+}
+
 #[derive(Debug, Clone)]
 struct NameBind;
 
@@ -44,6 +52,16 @@ impl Context {
             (newone, name)
         }
     }
+    fn add_name(&self, name: &str) -> Context {
+        let mut newone = self.clone();
+        newone.0.push((name.to_string(), NameBind));
+        newone
+    }
+    fn name_to_index(&self, name: &str) -> Option<usize> {
+        // index_to_name と対になる変換。Vec の後方が最も内側の束縛なので、
+        // 後ろから探すことで最も内側の同名束縛が優先される。
+        self.0.iter().rev().position(|x| x.0 == name)
+    }
 }
 
 impl Term {
@@ -119,12 +137,252 @@ impl Term {
         }
     }
 
+    fn eval1_by_name(&self) -> Result<Term, String> {
+        use self::Term::*;
+        if let App(ref t1, ref t2) = *self {
+            match t1.as_ref() {
+                &Abs(_, ref t12) => Ok(t12.subst_top(t2)),
+                _ => {
+                    let t1 = t1.eval1_by_name()?;
+                    Ok(App(Rc::new(t1), t2.clone()))
+                }
+            }
+        } else {
+            Err(format!("No rule applies: {:?}", self))
+        }
+    }
+
+    fn eval1_normal_order(&self) -> Result<Term, String> {
+        use self::Term::*;
+        match *self {
+            App(ref t1, ref t2) => {
+                match t1.as_ref() {
+                    &Abs(_, ref t12) => Ok(t12.subst_top(t2)),
+                    _ => match t1.eval1_normal_order() {
+                        Ok(t1) => Ok(App(Rc::new(t1), t2.clone())),
+                        Err(_) => {
+                            let t2 = t2.eval1_normal_order()?;
+                            Ok(App(t1.clone(), Rc::new(t2)))
+                        }
+                    }
+                }
+            }
+            Abs(ref name, ref t) => {
+                let t = t.eval1_normal_order()?;
+                Ok(Abs(name.clone(), Rc::new(t)))
+            }
+            Var(..) => Err(format!("No rule applies: {:?}", self))
+        }
+    }
+
+    pub fn eval_with(&self, strategy: Strategy) -> Term {
+        let eval1 = match strategy {
+            Strategy::CallByValue => Term::eval1,
+            Strategy::CallByName => Term::eval1_by_name,
+            Strategy::NormalOrder => Term::eval1_normal_order
+        };
+        let mut t = self.clone();
+        loop {
+            match eval1(&t) {
+                Ok(next) => t = next,
+                Err(_) => return t
+            }
+        }
+    }
+
     pub fn eval(&self) -> Term {
-        match self.eval1() {
-            Ok(t) => t.eval(),
-            Err(_) => self.clone()
+        self.eval_with(Strategy::CallByValue)
+    }
+
+    // eval1 を繰り返し適用して得られる簡約列をそのまま列挙するイテレータ。
+    // 最初の要素は self 自身で、以後は簡約が可能な限り続き、どの規則も
+    // 適用できなくなったところで終わる。
+    pub fn steps(&self) -> impl Iterator<Item = Term> {
+        ::std::iter::successors(Some(self.clone()), |t| t.eval1().ok())
+    }
+
+    pub fn trace(&self, ctx: &Context) -> String {
+        self.steps().map(|t| t.show(ctx)).collect::<Vec<_>>().join(" --> ")
+    }
+
+    // インタラクションネットにコンパイルして簡約する、共有を保つ最適簡約版の
+    // eval。閉じた項は abs の中まで簡約して正規形を返すので、eval と同じ
+    // 結果になるのは eval の結果が abs の中に簡約基を残さない場合に限る。
+    // 複数回使われる束縛変数に渡された引数は共有され、一度だけ簡約される。
+    // 開いた項は通常の eval に委ねる。
+    pub fn eval_optimal(&self) -> Term {
+        if super::inet::is_closed(self) {
+            super::inet::eval_optimal(self)
+        } else {
+            self.eval()
+        }
+    }
+
+    pub fn restore_names(&self, ctx: &Context) -> NamedTerm {
+        use self::Term::*;
+        match *self {
+            Var(x, n) => {
+                if ctx.len() == n {
+                    NamedTerm::Var(ctx.index_to_name(x).clone())
+                } else {
+                    panic!("bad index. Context len is {} but var has {}.", ctx.len(), n)
+                }
+            }
+            Abs(ref name, ref t) => {
+                let (ctx, name) = ctx.pick_fresh_name(name);
+                NamedTerm::Abs(name, Rc::new(t.restore_names(&ctx)))
+            }
+            App(ref t1, ref t2) => NamedTerm::App(Rc::new(t1.restore_names(ctx)), Rc::new(t2.restore_names(ctx)))
+        }
+    }
+}
+
+// 名前付きの項。パーズ結果や人間が書いたプログラムはこちらで表現し、
+// remove_names で通常の(名前なし) Term に変換してから評価する。
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum NamedTerm {
+    Var(String),
+    Abs(String, Rc<NamedTerm>),
+    App(Rc<NamedTerm>, Rc<NamedTerm>)
+}
+
+impl NamedTerm {
+    pub fn remove_names(&self, ctx: &Context) -> Result<Term, String> {
+        fn walk(t: &NamedTerm, ctx: &Context) -> Result<Term, String> {
+            use self::NamedTerm::*;
+            match *t {
+                Var(ref name) => {
+                    match ctx.name_to_index(name) {
+                        Some(x) => Ok(Term::Var(x, ctx.len())),
+                        None => Err(format!("unbound variable name: {}", name))
+                    }
+                }
+                Abs(ref name, ref t) => {
+                    let inner = ctx.add_name(name);
+                    let t = walk(t, &inner)?;
+                    Ok(Term::Abs(name.clone(), Rc::new(t)))
+                }
+                App(ref t1, ref t2) => Ok(Term::App(Rc::new(walk(t1, ctx)?), Rc::new(walk(t2, ctx)?)))
+            }
+        }
+        walk(self, ctx)
+    }
+}
+
+// 具象構文を NamedTerm に読み込む再帰下降パーザ。
+// term     ::= "\" ID "." term | appTerm
+// appTerm  ::= aTerm+                 (左結合。abs の本体より強く束縛する)
+// aTerm    ::= "(" term ")" | ID
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Lambda,
+    Dot,
+    Ident(String)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' | '\n' | '\r' => { chars.next(); }
+            '(' => { chars.next(); tokens.push(Token::LParen); }
+            ')' => { chars.next(); tokens.push(Token::RParen); }
+            '\\' => { chars.next(); tokens.push(Token::Lambda); }
+            '.' => { chars.next(); tokens.push(Token::Dot); }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' || c == '\'' {
+                        name.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(name));
+            }
+            _ => return Err(format!("unexpected character: {}", c))
         }
     }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn advance(&mut self) -> Option<&Token> {
+        let t = self.tokens.get(self.pos);
+        self.pos += 1;
+        t
+    }
+    fn expect(&mut self, tok: &Token) -> Result<(), String> {
+        match self.advance() {
+            Some(t) if t == tok => Ok(()),
+            other => Err(format!("expected {:?} but got {:?}", tok, other))
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<NamedTerm, String> {
+        match self.peek() {
+            Some(&Token::Lambda) => {
+                self.advance();
+                let name = match self.advance() {
+                    Some(&Token::Ident(ref name)) => name.clone(),
+                    other => return Err(format!("expected identifier after '\\' but got {:?}", other))
+                };
+                self.expect(&Token::Dot)?;
+                // 本体は行き着くところまで右に伸びる
+                let body = self.parse_term()?;
+                Ok(NamedTerm::Abs(name, Rc::new(body)))
+            }
+            _ => self.parse_app_term()
+        }
+    }
+
+    fn parse_app_term(&mut self) -> Result<NamedTerm, String> {
+        let mut t = self.parse_atom_term()?;
+        loop {
+            match self.peek() {
+                Some(&Token::LParen) | Some(&Token::Ident(_)) => {
+                    let arg = self.parse_atom_term()?;
+                    t = NamedTerm::App(Rc::new(t), Rc::new(arg));
+                }
+                _ => break
+            }
+        }
+        Ok(t)
+    }
+
+    fn parse_atom_term(&mut self) -> Result<NamedTerm, String> {
+        match self.advance() {
+            Some(&Token::LParen) => {
+                let t = self.parse_term()?;
+                self.expect(&Token::RParen)?;
+                Ok(t)
+            }
+            Some(&Token::Ident(ref name)) => Ok(NamedTerm::Var(name.clone())),
+            other => Err(format!("unexpected token: {:?}", other))
+        }
+    }
+}
+
+pub fn parse(input: &str, ctx: &Context) -> Result<Term, String> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0 };
+    let named = parser.parse_term()?;
+    if parser.pos != tokens.len() {
+        return Err(format!("unexpected trailing tokens at position {}", parser.pos));
+    }
+    named.remove_names(ctx)
 }
 
 #[cfg(test)]
@@ -140,6 +398,14 @@ mod tests {
         App(Rc::new(t1), Rc::new(t2))
     }
 
+    fn named_abs(name: &str, t: NamedTerm) -> NamedTerm {
+        NamedTerm::Abs(name.to_string(), Rc::new(t))
+    }
+
+    fn named_app(t1: NamedTerm, t2: NamedTerm) -> NamedTerm {
+        NamedTerm::App(Rc::new(t1), Rc::new(t2))
+    }
+
     #[test]
     fn term_show() {
         let context = Context::new(&["x"]);
@@ -199,4 +465,116 @@ mod tests {
         }
     }
 
+    #[test]
+    fn named_term_remove_names() {
+        let context = Context::new(&["x"]);
+        // x は自由変数として文脈から解決される
+        assert_eq!(NamedTerm::Var("x".to_string()).remove_names(&context).unwrap(), Var(0, 1));
+        // λy. x
+        assert_eq! {
+            named_abs("y", NamedTerm::Var("x".to_string())).remove_names(&context).unwrap(),
+            abs("y", Var(1, 2))
+        }
+        // λf. λx. f x  (内側の x は束縛変数)
+        assert_eq! {
+            named_abs("f", named_abs("x",
+                named_app(NamedTerm::Var("f".to_string()), NamedTerm::Var("x".to_string()))
+            )).remove_names(&context).unwrap(),
+            abs("f", abs("x", app(Var(1, 3), Var(0, 3))))
+        }
+        assert!(NamedTerm::Var("y".to_string()).remove_names(&context).is_err());
+    }
+
+    #[test]
+    fn term_restore_names() {
+        let context = Context::new(&["x"]);
+        assert_eq!(Var(0, 1).restore_names(&context), NamedTerm::Var("x".to_string()));
+        assert_eq! {
+            abs("y", Var(1, 2)).restore_names(&context),
+            named_abs("y", NamedTerm::Var("x".to_string()))
+        }
+        // 束縛変数名がぶつかるときは show と同様にフレッシュな名前が選ばれる
+        assert_eq! {
+            abs("x", Var(0, 2)).restore_names(&context),
+            named_abs("x'", NamedTerm::Var("x'".to_string()))
+        }
+    }
+
+    #[test]
+    fn named_term_round_trip() {
+        let context = Context::new(&["x"]);
+        // (λf. λg. f g) (λz. z)   (束縛変数名が文脈の自由変数と衝突しない場合は
+        // remove_names/restore_names を往復しても元の項に戻る)
+        let t = named_app(
+            named_abs("f", named_abs("g", named_app(NamedTerm::Var("f".to_string()), NamedTerm::Var("g".to_string())))),
+            named_abs("z", NamedTerm::Var("z".to_string()))
+        );
+        let nameless = t.clone().remove_names(&context).unwrap();
+        assert_eq!(nameless.restore_names(&context), t);
+    }
+
+    #[test]
+    fn test_parse() {
+        let empty = Context::new::<&str>(&[]);
+        // application は左結合で abs の本体より強く束縛する
+        assert_eq! {
+            parse("(\\f. \\x. f x) (\\z. z)", &empty).unwrap(),
+            app(abs("f", abs("x", app(Var(1, 2), Var(0, 2)))), abs("z", Var(0, 1)))
+        }
+        assert_eq! {
+            parse("\\x. \\y. x y z", &Context::new(&["z"])).unwrap(),
+            abs("x", abs("y", app(app(Var(1, 3), Var(0, 3)), Var(2, 3))))
+        }
+        assert!(parse("\\x. x y", &empty).is_err());
+        assert!(parse("(\\x. x", &empty).is_err());
+    }
+
+    #[test]
+    fn test_eval_with() {
+        let ctx = Context::new(&["z"]);
+        // (λx. λy. x) z : 引数 z は値(Abs)ではないので call-by-value では簡約できない
+        let t = parse("(\\x. \\y. x) z", &ctx).unwrap();
+        assert_eq!(t.eval_with(Strategy::CallByValue), t);
+        // call-by-name と normal-order は引数が値でなくても β 簡約するので、
+        // β 正規形 "λy. z" に到達する
+        let expected = parse("\\y. z", &ctx).unwrap();
+        assert_eq!(t.eval_with(Strategy::CallByName), expected);
+        assert_eq!(t.eval_with(Strategy::NormalOrder), expected);
+    }
+
+    #[test]
+    fn test_steps_and_trace() {
+        let empty = Context::new::<&str>(&[]);
+        // (λf. λx. f x) (λz. z) (λu. u) => (λx. (λz.z) x) (λu.u) => (λz.z) (λu.u) => λu.u
+        let t = parse("(\\f. \\x. f x) (\\z. z) (\\u. u)", &empty).unwrap();
+        let steps: Vec<Term> = t.steps().collect();
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0], t);
+        assert_eq!(*steps.last().unwrap(), t.eval());
+        let expected_trace = steps.iter().map(|t| t.show(&empty)).collect::<Vec<_>>().join(" --> ");
+        assert_eq!(t.trace(&empty), expected_trace);
+    }
+
+    #[test]
+    fn test_eval_optimal_agrees_with_eval() {
+        let empty = Context::new::<&str>(&[]);
+
+        let t = parse("(\\f. \\x. f x) (\\z. \\w. z) (\\u. u)", &empty).unwrap();
+        assert_eq!(t.eval_optimal(), t.eval());
+
+        // 引数 (λy.y) を2回使う自己適用。DUP agent による複製を経由する。
+        let t = parse("(\\x. x x) (\\y. y)", &empty).unwrap();
+        assert_eq!(t.eval_optimal(), t.eval());
+
+        // f を2回使う twice の適用。共有された冗長基は一度だけ簡約される。
+        // x まで適用して beta 基が残らない形にしないと、eval (call-by-value)
+        // は abs の中まで簡約しないので eval_optimal と食い違ってしまう。
+        let t = parse("(\\f. \\x. f (f x)) (\\y. y) (\\z. z)", &empty).unwrap();
+        assert_eq!(t.eval_optimal(), t.eval());
+
+        // 開いた項は通常の eval にフォールバックする
+        let open = parse("\\x. x y", &Context::new(&["y"])).unwrap();
+        assert_eq!(open.eval_optimal(), open.eval());
+    }
+
 }